@@ -0,0 +1,129 @@
+use std::env;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use glob::glob;
+use serde::Serialize;
+
+use crate::SunState;
+
+/// A config override sent over Alacritty's IPC socket, mirroring the
+/// `alacritty msg config` payload: a list of TOML snippets applied on top of
+/// the running config.
+#[derive(Serialize)]
+struct ConfigOptions {
+    options: Vec<String>,
+    reset: bool,
+}
+
+/// Mirrors Alacritty's own `SocketMessage`, which serde's default
+/// (externally tagged) representation renders as `{"Config":{...}}` -
+/// there's no `type`/`payload` wrapping on the wire.
+#[derive(Serialize)]
+enum SocketMessage {
+    Config(ConfigOptions),
+}
+
+/// Find the IPC sockets of every running Alacritty instance: globs the
+/// runtime directory the way `set_running_nvim_sessions` globs
+/// `/tmp/nvim*/0` for Neovim instances, and adds `ALACRITTY_SOCKET` for the
+/// instance we're running inside of (if any) when the glob missed it.
+fn discover_sockets() -> Result<Vec<PathBuf>> {
+    let runtime_dir = env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".into());
+    let pattern = format!("{}/Alacritty-*.sock", runtime_dir);
+
+    let mut sockets: Vec<PathBuf> = glob(&pattern)?.filter_map(|entry| entry.ok()).collect();
+
+    if let Ok(socket) = env::var("ALACRITTY_SOCKET") {
+        let socket = PathBuf::from(socket);
+        if !sockets.contains(&socket) {
+            sockets.push(socket);
+        }
+    }
+
+    Ok(sockets)
+}
+
+/// Dark-theme and light-theme background/foreground colors, as `(r, g, b)`
+/// triples, to interpolate between for the intermediate twilight grades.
+const NIGHT_BACKGROUND: (u8, u8, u8) = (0x28, 0x2c, 0x34);
+const NIGHT_FOREGROUND: (u8, u8, u8) = (0xab, 0xb2, 0xbf);
+const DAY_BACKGROUND: (u8, u8, u8) = (0xfa, 0xfa, 0xfa);
+const DAY_FOREGROUND: (u8, u8, u8) = (0x38, 0x3a, 0x42);
+
+/// How far `state` sits between fully dark (0.0) and fully light (1.0), used
+/// to interpolate the color values Alacritty's IPC socket can set freely -
+/// unlike backends limited to a fixed pair of named themes, this one can
+/// actually render the graded scale.
+fn lightness(state: &SunState) -> f64 {
+    match state {
+        SunState::Night => 0.0,
+        SunState::AstronomicalDawn | SunState::AstronomicalDusk => 0.15,
+        SunState::NauticalDawn | SunState::NauticalDusk => 0.35,
+        SunState::CivilDawn | SunState::CivilDusk => 0.65,
+        SunState::Day => 1.0,
+    }
+}
+
+fn lerp_channel(dark: u8, light: u8, t: f64) -> u8 {
+    (dark as f64 + (light as f64 - dark as f64) * t).round() as u8
+}
+
+fn lerp_color(dark: (u8, u8, u8), light: (u8, u8, u8), t: f64) -> String {
+    format!(
+        "'#{:02x}{:02x}{:02x}'",
+        lerp_channel(dark.0, light.0, t),
+        lerp_channel(dark.1, light.1, t),
+        lerp_channel(dark.2, light.2, t),
+    )
+}
+
+fn colors_for(state: &SunState) -> [(&'static str, String); 2] {
+    let t = lightness(state);
+    [
+        ("colors.primary.background", lerp_color(NIGHT_BACKGROUND, DAY_BACKGROUND, t)),
+        ("colors.primary.foreground", lerp_color(NIGHT_FOREGROUND, DAY_FOREGROUND, t)),
+    ]
+}
+
+/// Push the new color scheme to every running Alacritty window over its IPC
+/// socket, so already-open windows update immediately instead of waiting for
+/// a restart.
+pub fn set_running_alacritty_sessions(state: &SunState) -> Result<()> {
+    let options = IntoIterator::into_iter(colors_for(state))
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+
+    let message = SocketMessage::Config(ConfigOptions { options, reset: false });
+    let payload = serde_json::to_string(&message)?;
+
+    for socket_path in discover_sockets()? {
+        let mut stream = UnixStream::connect(&socket_path)?;
+        stream.write_all(payload.as_bytes())?;
+        stream.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_message_matches_alacritty_wire_format() {
+        let message = SocketMessage::Config(ConfigOptions {
+            options: vec!["colors.primary.background='#282c34'".to_string()],
+            reset: false,
+        });
+
+        let payload = serde_json::to_string(&message).unwrap();
+
+        assert_eq!(
+            payload,
+            r#"{"Config":{"options":["colors.primary.background='#282c34'"],"reset":false}}"#
+        );
+    }
+}
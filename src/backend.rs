@@ -0,0 +1,139 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+
+use anyhow::Result;
+
+use crate::settings::Settings;
+use crate::{set_running_nvim_sessions, set_static_alacritty_config, set_static_nvim_config, SunState};
+use crate::alacritty;
+
+/// Something `auto_daytime` can push a light/dark theme change to.
+pub trait Backend {
+    fn apply(&self, state: &SunState) -> Result<()>;
+}
+
+pub struct NvimBackend<'a> {
+    pub settings: &'a Settings,
+}
+
+impl<'a> Backend for NvimBackend<'a> {
+    fn apply(&self, state: &SunState) -> Result<()> {
+        set_running_nvim_sessions(state)?;
+        set_static_nvim_config(state, self.settings)
+    }
+}
+
+pub struct AlacrittyBackend<'a> {
+    pub settings: &'a Settings,
+}
+
+impl<'a> Backend for AlacrittyBackend<'a> {
+    fn apply(&self, state: &SunState) -> Result<()> {
+        set_static_alacritty_config(state, self.settings)
+    }
+}
+
+pub struct AlacrittyIpcBackend;
+
+impl Backend for AlacrittyIpcBackend {
+    fn apply(&self, state: &SunState) -> Result<()> {
+        alacritty::set_running_alacritty_sessions(state)
+    }
+}
+
+pub struct TmuxBackend;
+
+impl Backend for TmuxBackend {
+    fn apply(&self, state: &SunState) -> Result<()> {
+        let style = if state.is_light() { "light" } else { "dark" };
+
+        // Best-effort: there may be no tmux server running at all, which is
+        // fine, this backend is simply a no-op then.
+        let _ = Command::new("tmux")
+            .args(["set-option", "-g", "@auto_daytime_mode", style])
+            .status();
+
+        Ok(())
+    }
+}
+
+pub struct KittyBackend;
+
+impl Backend for KittyBackend {
+    fn apply(&self, state: &SunState) -> Result<()> {
+        if env::var("KITTY_LISTEN_ON").is_err() {
+            return Ok(());
+        }
+
+        let theme = if state.is_light() { "Builtin Pencil Light" } else { "Builtin Pencil Dark" };
+
+        Command::new("kitty").args(["@", "set-colors", "-a", theme]).status()?;
+
+        Ok(())
+    }
+}
+
+pub struct BatBackend<'a> {
+    pub settings: &'a Settings,
+}
+
+impl<'a> Backend for BatBackend<'a> {
+    fn apply(&self, state: &SunState) -> Result<()> {
+        let theme = if state.is_light() { "OneHalfLight" } else { "OneHalfDark" };
+
+        if let Some(parent) = self.settings.bat_config.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.settings.bat_config, format!("--theme=\"{}\"\n", theme))?;
+
+        Ok(())
+    }
+}
+
+pub struct CommandBackend {
+    pub command: String,
+}
+
+impl Backend for CommandBackend {
+    fn apply(&self, state: &SunState) -> Result<()> {
+        let state_string = if state.is_light() { "light" } else { "dark" };
+
+        Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env("AUTO_DAYTIME_STATE", state_string)
+            .env("AUTO_DAYTIME_GRADE", format!("{:?}", state))
+            .status()?;
+
+        Ok(())
+    }
+}
+
+/// Build the backends selected by `settings.targets`, in order, skipping any
+/// name we don't recognize.
+pub fn enabled_backends(settings: &Settings) -> Vec<Box<dyn Backend + '_>> {
+    settings
+        .targets
+        .iter()
+        .filter_map(|target| build_backend(target, settings))
+        .collect()
+}
+
+/// Recognized target names: `nvim`, `alacritty`, `alacritty_ipc`, `tmux`,
+/// `kitty`, `bat`, and `command:<shell command>` for the generic hook.
+fn build_backend<'a>(target: &str, settings: &'a Settings) -> Option<Box<dyn Backend + 'a>> {
+    if let Some(command) = target.strip_prefix("command:") {
+        return Some(Box::new(CommandBackend { command: command.to_string() }));
+    }
+
+    match target {
+        "nvim" => Some(Box::new(NvimBackend { settings })),
+        "alacritty" => Some(Box::new(AlacrittyBackend { settings })),
+        "alacritty_ipc" => Some(Box::new(AlacrittyIpcBackend)),
+        "tmux" => Some(Box::new(TmuxBackend)),
+        "kitty" => Some(Box::new(KittyBackend)),
+        "bat" => Some(Box::new(BatBackend { settings })),
+        _ => None,
+    }
+}
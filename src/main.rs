@@ -1,12 +1,8 @@
-use structopt;
 use std::path::PathBuf;
 use clap::arg_enum;
 use structopt::StructOpt;
-use shellexpand;
-use anyhow::Result;
-use reqwest;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use serde_json;
 use chrono::prelude::*;
 use neovim_lib::{Neovim, NeovimApi, Session};
 use glob::glob;
@@ -14,6 +10,13 @@ use std::fs::{remove_file,OpenOptions, File};
 use std::io::prelude::*;
 use regex::Regex;
 
+mod settings;
+use settings::Settings;
+mod solar;
+mod alacritty;
+mod backend;
+mod terminal_theme;
+
 #[derive(Serialize, Deserialize, Debug)]
 struct IPInfo {
     ip: String,
@@ -49,10 +52,55 @@ struct SunInfoResponse {
 }
 
 arg_enum! {
-    #[derive(Debug,PartialEq)]
-    enum SunState {
-        Up,
-        Down
+    #[derive(Debug,PartialEq,Clone,Copy)]
+    pub enum SunState {
+        Night,
+        AstronomicalDawn,
+        NauticalDawn,
+        CivilDawn,
+        Day,
+        CivilDusk,
+        NauticalDusk,
+        AstronomicalDusk,
+    }
+}
+
+impl SunState {
+    /// Collapse the graded scale down to the binary light/dark theme that
+    /// backends without an intermediate theme (Neovim, the Alacritty YAML
+    /// anchors, bat, tmux, ...) can apply.
+    pub fn is_light(&self) -> bool {
+        matches!(self, SunState::Day | SunState::CivilDawn | SunState::CivilDusk)
+    }
+
+    /// Stable name used to persist the grade to `settings.state_file`,
+    /// independent of `arg_enum!`'s `Display`/`FromStr` impls (which are
+    /// tuned for CLI parsing, not round-tripping).
+    fn as_str(&self) -> &'static str {
+        match self {
+            SunState::Night => "Night",
+            SunState::AstronomicalDawn => "AstronomicalDawn",
+            SunState::NauticalDawn => "NauticalDawn",
+            SunState::CivilDawn => "CivilDawn",
+            SunState::Day => "Day",
+            SunState::CivilDusk => "CivilDusk",
+            SunState::NauticalDusk => "NauticalDusk",
+            SunState::AstronomicalDusk => "AstronomicalDusk",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        Some(match s {
+            "Night" => SunState::Night,
+            "AstronomicalDawn" => SunState::AstronomicalDawn,
+            "NauticalDawn" => SunState::NauticalDawn,
+            "CivilDawn" => SunState::CivilDawn,
+            "Day" => SunState::Day,
+            "CivilDusk" => SunState::CivilDusk,
+            "NauticalDusk" => SunState::NauticalDusk,
+            "AstronomicalDusk" => SunState::AstronomicalDusk,
+            _ => return None,
+        })
     }
 }
 
@@ -61,47 +109,86 @@ arg_enum! {
 #[derive(StructOpt, Debug)]
 #[structopt(name = "basic")]
 struct Opt {
-    /// Set alacritty config path
-    #[structopt(short, long, default_value = "~/.config/alacritty/alacritty.yml")]
-    alacritty: PathBuf,
+    /// Set alacritty config path, overriding the config file and environment
+    #[structopt(short, long)]
+    alacritty: Option<PathBuf>,
 
-    /// Set nvim config path
-    #[structopt(short, long, default_value = "~/.config/nvim/init.vim")]
-    nvim_init: PathBuf,
+    /// Set nvim config path, overriding the config file and environment
+    #[structopt(short, long)]
+    nvim_init: Option<PathBuf>,
 
     /// Force a light or dark mode
     #[structopt(short, long, possible_values = &SunState::variants(), case_insensitive = true)]
     force: Option<SunState>,
+
+    /// Keep running instead of exiting, switching themes exactly at each dawn/dusk boundary when latitude/longitude are configured, otherwise checking every 15 minutes
+    #[structopt(short, long)]
+    daemon: bool,
+
+    /// Derive light/dark from the terminal's actual OSC 11 background color instead of the sun
+    #[structopt(short, long)]
+    terminal_theme: bool,
 }
 
 fn get_local_dt(formatted_time: &str) -> Result<DateTime<Local>> {
     let naive_time = NaiveTime::parse_from_str(formatted_time, "%l:%M:%S %p")?;
-    let today = Local::today().naive_local();
-    let utc_time = Utc.from_utc_date(&today).and_time(naive_time).unwrap();
+    let today = Local::now().naive_local().date();
+    let utc_time = Utc.from_utc_datetime(&today.and_time(naive_time));
     let local_time = Local.from_utc_datetime(&utc_time.naive_local());
     Ok(local_time)
 }
 
-fn get_local_sun_state() -> Result<SunState> {
-    let body = reqwest::blocking::get("https://ipinfo.io")?.text()?;
+fn get_local_sun_state(settings: &Settings) -> Result<SunState> {
+    if settings.use_terminal_theme {
+        return terminal_theme::get_terminal_sun_state();
+    }
+
+    if let (Some(lat), Some(lon)) = (settings.latitude, settings.longitude) {
+        return Ok(solar::get_offline_sun_state(lat, lon));
+    }
+
+    get_remote_sun_state(settings)
+}
+
+fn get_remote_sun_state(settings: &Settings) -> Result<SunState> {
+    let body = reqwest::blocking::get(&settings.geocoding_api_url)?.text()?;
     let info: IPInfo = serde_json::from_str(&body)?;
     let coord: Vec<f64> = info.loc.split(",").map(|r| r.parse::<f64>().unwrap()).collect();
 
-    let sun_info_body = reqwest::blocking::get(format!("https://api.sunrise-sunset.org/json?lat={}&lng={}", coord.get(0).unwrap(), coord.get(1).unwrap()))?.text()?;
+    let sun_api_url = format!("{}?lat={}&lng={}", settings.sun_api_url, coord.first().unwrap(), coord.get(1).unwrap());
+    let sun_info_body = reqwest::blocking::get(&sun_api_url)?.text()?;
     let sun_info: SunInfoResponse = serde_json::from_str(&sun_info_body)?;
 
-    let local_sunrise = get_local_dt(&sun_info.results.sunrise)?;
-    let local_sunset = get_local_dt(&sun_info.results.sunset)?;
+    grade_from_sun_info(&sun_info.results)
+}
 
-    let state = if local_sunrise <= Local::now() && Local::now() < local_sunset {
-        SunState::Up
-    } else {
-        SunState::Down
-    };
-    Ok(state)
+/// Bucket `Local::now()` against the dawn/dusk boundaries the API already
+/// gave us, earliest first, picking the grade that begins at the last
+/// boundary already reached.
+fn grade_from_sun_info(sun_info: &SunInfo) -> Result<SunState> {
+    let boundaries = [
+        (&sun_info.astronomical_twilight_begin, SunState::AstronomicalDawn),
+        (&sun_info.nautical_twilight_begin, SunState::NauticalDawn),
+        (&sun_info.civil_twilight_begin, SunState::CivilDawn),
+        (&sun_info.sunrise, SunState::Day),
+        (&sun_info.sunset, SunState::CivilDusk),
+        (&sun_info.civil_twilight_end, SunState::NauticalDusk),
+        (&sun_info.nautical_twilight_end, SunState::AstronomicalDusk),
+        (&sun_info.astronomical_twilight_end, SunState::Night),
+    ];
+
+    let now = Local::now();
+    let mut grade = SunState::Night;
+    for (formatted_time, boundary_grade) in boundaries {
+        if get_local_dt(formatted_time)? <= now {
+            grade = boundary_grade;
+        }
+    }
+
+    Ok(grade)
 }
 
-fn set_running_nvim_sessions(state: &SunState) -> Result<()>{
+pub(crate) fn set_running_nvim_sessions(state: &SunState) -> Result<()>{
     // connect to all neovim instances
     // set the correct background, reload AirlineTheme
     for nvim_path in glob("/tmp/nvim*/0")? {
@@ -110,9 +197,10 @@ fn set_running_nvim_sessions(state: &SunState) -> Result<()>{
         session.start_event_loop();
         let mut nvim = Neovim::new(session);
 
-        match state {
-            SunState::Up => nvim.command("set bg=light")?,
-            SunState::Down => nvim.command("set bg=dark")?,
+        if state.is_light() {
+            nvim.command("set bg=light")?
+        } else {
+            nvim.command("set bg=dark")?
         };
 
         
@@ -123,55 +211,64 @@ fn set_running_nvim_sessions(state: &SunState) -> Result<()>{
     Ok(())
 }
 
-fn get_daylight_config() -> Result<PathBuf> {
-    let daylight_config: String = shellexpand::tilde("~/.daylight.vim").into();
-    let daylight_config_path = PathBuf::from(daylight_config);
+fn get_daylight_config(settings: &Settings) -> Result<PathBuf> {
+    let daylight_config_path = settings.daylight_config.clone();
 
     if !daylight_config_path.is_file() {
-        println!("You really need a ~/.daylight.vim for this to work!");
-        let mut daylight_config_file = OpenOptions::new().create(true).write(true).open(&daylight_config_path)?;
-        daylight_config_file.write_all("set bg=light\n".as_bytes());
+        println!("You really need a {:?} for this to work!", daylight_config_path);
+        let mut daylight_config_file = OpenOptions::new().create(true).write(true).truncate(true).open(&daylight_config_path)?;
+        daylight_config_file.write_all("set bg=light\n".as_bytes())?;
     }
 
     Ok(daylight_config_path)
 }
 
-fn get_static_daylight() -> Result<SunState> {
-    let daylight_config = get_daylight_config()?;
-    let mut daylight_file = File::open(&daylight_config)?;
-    let mut daylight_content = String::new();
-    daylight_file.read_to_string(&mut daylight_content)?;
+/// Read back the last grade `sync_state` actually applied, independent of
+/// any one backend's own config file (e.g. `~/.daylight.vim` is only ever
+/// written by the nvim backend, so it can't stand in for "applied" once a
+/// user picks `targets` without `nvim` in it). `None` means nothing has been
+/// applied yet, so the first run should always apply unconditionally.
+fn get_applied_state(settings: &Settings) -> Result<Option<SunState>> {
+    if !settings.state_file.is_file() {
+        return Ok(None);
+    }
 
-    let up_pattern = Regex::new("dark")?;
-    if up_pattern.is_match(&daylight_content){
-        Ok(SunState::Down)
-    } else {
-        Ok(SunState::Up)
+    let mut contents = String::new();
+    File::open(&settings.state_file)?.read_to_string(&mut contents)?;
+    Ok(SunState::from_str(contents.trim()))
+}
+
+fn set_applied_state(state: &SunState, settings: &Settings) -> Result<()> {
+    if let Some(parent) = settings.state_file.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+    std::fs::write(&settings.state_file, state.as_str())?;
+    Ok(())
 }
 
-fn set_static_nvim_config(state: &SunState) -> Result<()> {
-    let daylight_config_path = get_daylight_config()?;
+pub(crate) fn set_static_nvim_config(state: &SunState, settings: &Settings) -> Result<()> {
+    let daylight_config_path = get_daylight_config(settings)?;
 
     let mut daylight_file = OpenOptions::new()
         .append(false)
         .create(true)
         .write(true)
+        .truncate(true)
         .read(false)
         .open(&daylight_config_path)?;
 
-    match state {
-        SunState::Up => daylight_file.write_all(b"set bg=light\n")?,
-        SunState::Down => daylight_file.write_all(b"set bg=dark\n")?,
+    if state.is_light() {
+        daylight_file.write_all(b"set bg=light\n")?;
+    } else {
+        daylight_file.write_all(b"set bg=dark\n")?;
     }
 
     Ok(())
 }
 
-fn set_static_alacritty_config(state: &SunState) -> Result<()> {
-    let alacritty_config: String = shellexpand::tilde("~/.config/alacritty/alacritty.yml").into();
-    let alacritty_config_path = PathBuf::from(&alacritty_config);
-    
+pub(crate) fn set_static_alacritty_config(state: &SunState, settings: &Settings) -> Result<()> {
+    let alacritty_config_path = settings.alacritty.clone();
+
     if ! alacritty_config_path.is_file()  {
         return Err(anyhow!("Alacritty config missing."));
     }
@@ -188,10 +285,7 @@ fn set_static_alacritty_config(state: &SunState) -> Result<()> {
 
     let color_line = Regex::new(r"colors: \*(([_\w]+)(light|dark)([_\w]+))")?;
 
-    let state_string: String = match state {
-        SunState::Up => "light".into(),
-        SunState::Down => "dark".into(),
-    };
+    let state_string: String = if state.is_light() { "light".into() } else { "dark".into() };
 
     let updated_config: Vec<String> = config.lines().map(|line|{
         match color_line.captures(line) {
@@ -209,20 +303,54 @@ fn set_static_alacritty_config(state: &SunState) -> Result<()> {
     Ok(())
 }
 
+/// Apply `state` to every enabled backend, but only when it differs from the
+/// grade we last applied — comparing the full graded `SunState`, not just
+/// `is_light()`, so every dawn/dusk boundary (not only the two light/dark
+/// crossings) triggers an apply.
+fn sync_state(state: &SunState, settings: &Settings) -> Result<()> {
+    if get_applied_state(settings)? == Some(*state) {
+        return Ok(());
+    }
+
+    for backend in backend::enabled_backends(settings) {
+        backend.apply(state)?;
+    }
+    set_applied_state(state, settings)
+}
+
+/// Poll interval used when no lat/lon is configured, so the daemon still
+/// notices a transition even though it can't compute one precisely.
+const DAEMON_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+fn run_daemon(settings: &Settings) -> Result<()> {
+    loop {
+        let state = get_local_sun_state(settings)?;
+        sync_state(&state, settings)?;
+
+        let sleep_duration = match (settings.latitude, settings.longitude) {
+            (Some(lat), Some(lon)) => {
+                let next = solar::next_transition(lat, lon, Local::now());
+                (next - Local::now()).to_std().unwrap_or(DAEMON_POLL_INTERVAL)
+            }
+            _ => DAEMON_POLL_INTERVAL,
+        };
+
+        std::thread::sleep(sleep_duration);
+    }
+}
+
 fn main() -> Result<()> {
     let opt = Opt::from_args();
+    let settings = Settings::load(&opt)?;
+
+    if opt.daemon {
+        return run_daemon(&settings);
+    }
 
     let state = match opt.force {
         Some(s) => s,
-        None => get_local_sun_state()?,
+        None => get_local_sun_state(&settings)?,
     };
 
-    let set_state = get_static_daylight()?;
-    if state != set_state {
-        set_running_nvim_sessions(&state)?;
-        set_static_nvim_config(&state)?;
-        set_static_alacritty_config(&state)?;
-    }
-
-    Ok(())
+    sync_state(&state, &settings)
 }
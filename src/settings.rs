@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+use std::fs;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::Opt;
+
+/// Shape of `~/.config/auto_daytime/config.toml`. Every field is optional so a
+/// user only needs to declare the values they want to override.
+#[derive(Debug, Default, Deserialize)]
+struct FileConfig {
+    alacritty: Option<PathBuf>,
+    nvim_init: Option<PathBuf>,
+    daylight_config: Option<PathBuf>,
+    bat_config: Option<PathBuf>,
+    state_file: Option<PathBuf>,
+    targets: Option<Vec<String>>,
+    geocoding_api_url: Option<String>,
+    sun_api_url: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    use_terminal_theme: Option<bool>,
+}
+
+/// Fully resolved configuration, built by layering defaults, the TOML config
+/// file, environment variables and CLI flags on top of each other, in that
+/// order, so later layers win.
+#[derive(Debug)]
+pub struct Settings {
+    pub alacritty: PathBuf,
+    pub nvim_init: PathBuf,
+    pub daylight_config: PathBuf,
+    pub bat_config: PathBuf,
+    /// Where the last-applied `SunState` is recorded, so `sync_state` can
+    /// tell whether a transition actually happened. Kept independent of any
+    /// one backend's own config file, since not every `targets` selection
+    /// includes a backend that persists state on its own.
+    pub state_file: PathBuf,
+    /// Names of the backends to theme, applied in order. See `backend::build_backend`
+    /// for the recognized names (`nvim`, `alacritty`, `alacritty_ipc`, `tmux`, `kitty`,
+    /// `bat`) plus `command:<shell command>` for the generic hook.
+    pub targets: Vec<String>,
+    pub geocoding_api_url: String,
+    pub sun_api_url: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// When true, derive `SunState` from the terminal's actual OSC 11
+    /// background color instead of the sun's position.
+    pub use_terminal_theme: bool,
+}
+
+impl Settings {
+    fn defaults() -> Self {
+        Settings {
+            alacritty: expand_tilde("~/.config/alacritty/alacritty.yml"),
+            nvim_init: expand_tilde("~/.config/nvim/init.vim"),
+            daylight_config: expand_tilde("~/.daylight.vim"),
+            bat_config: expand_tilde("~/.config/bat/config"),
+            state_file: expand_tilde("~/.cache/auto_daytime/state"),
+            targets: vec!["nvim".into(), "alacritty".into(), "alacritty_ipc".into()],
+            geocoding_api_url: "https://ipinfo.io".into(),
+            sun_api_url: "https://api.sunrise-sunset.org/json".into(),
+            latitude: None,
+            longitude: None,
+            use_terminal_theme: false,
+        }
+    }
+
+    fn merge_file(&mut self, file: FileConfig) {
+        if let Some(v) = file.alacritty {
+            self.alacritty = v;
+        }
+        if let Some(v) = file.nvim_init {
+            self.nvim_init = v;
+        }
+        if let Some(v) = file.daylight_config {
+            self.daylight_config = v;
+        }
+        if let Some(v) = file.bat_config {
+            self.bat_config = v;
+        }
+        if let Some(v) = file.state_file {
+            self.state_file = v;
+        }
+        if let Some(v) = file.targets {
+            self.targets = v;
+        }
+        if let Some(v) = file.geocoding_api_url {
+            self.geocoding_api_url = v;
+        }
+        if let Some(v) = file.sun_api_url {
+            self.sun_api_url = v;
+        }
+        if let Some(v) = file.latitude {
+            self.latitude = Some(v);
+        }
+        if let Some(v) = file.longitude {
+            self.longitude = Some(v);
+        }
+        if let Some(v) = file.use_terminal_theme {
+            self.use_terminal_theme = v;
+        }
+    }
+
+    fn merge_env(&mut self) {
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_ALACRITTY") {
+            self.alacritty = PathBuf::from(v);
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_NVIM_INIT") {
+            self.nvim_init = PathBuf::from(v);
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_DAYLIGHT_CONFIG") {
+            self.daylight_config = PathBuf::from(v);
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_BAT_CONFIG") {
+            self.bat_config = PathBuf::from(v);
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_STATE_FILE") {
+            self.state_file = PathBuf::from(v);
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_TARGETS") {
+            self.targets = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_GEOCODING_API_URL") {
+            self.geocoding_api_url = v;
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_SUN_API_URL") {
+            self.sun_api_url = v;
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_LATITUDE") {
+            self.latitude = v.parse().ok();
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_LONGITUDE") {
+            self.longitude = v.parse().ok();
+        }
+        if let Ok(v) = std::env::var("AUTO_DAYTIME_USE_TERMINAL_THEME") {
+            self.use_terminal_theme = v == "1" || v == "true";
+        }
+    }
+
+    fn merge_opt(&mut self, opt: &Opt) {
+        if let Some(v) = &opt.alacritty {
+            self.alacritty = v.clone();
+        }
+        if let Some(v) = &opt.nvim_init {
+            self.nvim_init = v.clone();
+        }
+        if opt.terminal_theme {
+            self.use_terminal_theme = true;
+        }
+    }
+
+    /// Build the effective settings for this run: defaults, then
+    /// `~/.config/auto_daytime/config.toml`, then `AUTO_DAYTIME_*` env vars,
+    /// then `opt`'s CLI flags, each layer overriding the previous one.
+    pub fn load(opt: &Opt) -> Result<Self> {
+        let mut settings = Settings::defaults();
+
+        let config_path = expand_tilde("~/.config/auto_daytime/config.toml");
+        if config_path.is_file() {
+            let contents = fs::read_to_string(&config_path)?;
+            let file_config: FileConfig = toml::from_str(&contents)?;
+            settings.merge_file(file_config);
+        }
+
+        settings.merge_env();
+        settings.merge_opt(opt);
+
+        Ok(settings)
+    }
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(path).into_owned())
+}
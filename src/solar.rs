@@ -0,0 +1,153 @@
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+
+use crate::SunState;
+
+/// Julian day of the Unix epoch (1970-01-01T00:00:00Z).
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2440587.5;
+/// Earth's axial tilt, in degrees.
+const EARTH_OBLIQUITY: f64 = 23.44;
+
+/// The official sunrise/sunset depression angle (accounts for atmospheric
+/// refraction and the sun's apparent radius), and the civil, nautical and
+/// astronomical twilight depression angles, all in degrees below the
+/// horizon. Paired with `SunState`'s variants in dawn/dusk order.
+const TWILIGHT_ANGLES: [f64; 4] = [0.833, 6.0, 12.0, 18.0];
+
+/// The computed rise/set pair for a given depression angle, day and
+/// location.
+pub struct SolarTimes {
+    pub rise: DateTime<Local>,
+    pub set: DateTime<Local>,
+}
+
+/// Result of the solar calculation: either a rise/set pair, or the polar-day
+/// / polar-night case where the sun never crosses that depression angle at
+/// all on the given day.
+pub enum SolarResult {
+    Times(SolarTimes),
+    AlwaysAbove,
+    AlwaysBelow,
+}
+
+/// Compute the rise/set times for `date` at `(lat, lon)` where the sun's
+/// center crosses `depression_degrees` below the horizon (0.833 for
+/// sunrise/sunset, 6/12/18 for civil/nautical/astronomical twilight),
+/// without any network access.
+///
+/// See <https://en.wikipedia.org/wiki/Sunrise_equation> for the formulas.
+pub fn times_at_depression(lat: f64, lon: f64, date: NaiveDate, depression_degrees: f64) -> SolarResult {
+    let jd = julian_day(date);
+    let n = (jd - 2451545.0 + 0.0008).round();
+
+    let j_star = n - lon / 360.0;
+    let mean_anomaly = (357.5291 + 0.98560028 * j_star).rem_euclid(360.0);
+    let m = mean_anomaly.to_radians();
+
+    let equation_of_center = 1.9148 * m.sin() + 0.0200 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_longitude = (mean_anomaly + equation_of_center + 282.9372).rem_euclid(360.0);
+    let lambda = ecliptic_longitude.to_radians();
+
+    let solar_transit = 2451545.0 + j_star + 0.0053 * m.sin() - 0.0069 * (2.0 * lambda).sin();
+
+    let declination_sin = lambda.sin() * EARTH_OBLIQUITY.to_radians().sin();
+    let declination = declination_sin.asin();
+
+    let latitude = lat.to_radians();
+    let cos_hour_angle = ((-depression_degrees).to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+
+    if cos_hour_angle > 1.0 {
+        return SolarResult::AlwaysBelow;
+    }
+    if cos_hour_angle < -1.0 {
+        return SolarResult::AlwaysAbove;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    let j_rise = solar_transit - hour_angle / 360.0;
+    let j_set = solar_transit + hour_angle / 360.0;
+
+    SolarResult::Times(SolarTimes {
+        rise: datetime_from_julian_day(j_rise),
+        set: datetime_from_julian_day(j_set),
+    })
+}
+
+fn julian_day(date: NaiveDate) -> f64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() as f64 / 86400.0 + UNIX_EPOCH_JULIAN_DAY
+}
+
+fn datetime_from_julian_day(jd: f64) -> DateTime<Local> {
+    let unix_seconds = ((jd - UNIX_EPOCH_JULIAN_DAY) * 86400.0).round() as i64;
+    Utc.timestamp_opt(unix_seconds, 0).unwrap().with_timezone(&Local)
+}
+
+/// All of `date`'s dawn/dusk boundary crossings at `(lat, lon)`, ordered
+/// earliest-first, paired with the grade that begins at that moment.
+fn boundaries_on(lat: f64, lon: f64, date: NaiveDate) -> Vec<(DateTime<Local>, SunState)> {
+    // A larger depression angle is crossed earlier in the morning (and later
+    // in the evening), so the narrowest angle (sunrise/sunset, index 0)
+    // pairs with the grade that begins at sunrise: `Day`.
+    let dawn_grades = [SunState::Day, SunState::CivilDawn, SunState::NauticalDawn, SunState::AstronomicalDawn];
+    let dusk_grades = [SunState::CivilDusk, SunState::NauticalDusk, SunState::AstronomicalDusk, SunState::Night];
+
+    let mut boundaries = Vec::new();
+    for (i, &angle) in TWILIGHT_ANGLES.iter().enumerate() {
+        if let SolarResult::Times(times) = times_at_depression(lat, lon, date, angle) {
+            boundaries.push((times.rise, dawn_grades[i]));
+            boundaries.push((times.set, dusk_grades[i]));
+        }
+    }
+
+    boundaries.sort_by_key(|(t, _)| *t);
+    boundaries
+}
+
+/// Grade the sky at `(lat, lon)` at `now`, using the boundaries that fall on
+/// `now`'s date and the day before/after to cover the edges of the day.
+pub fn grade_at(lat: f64, lon: f64, now: DateTime<Local>) -> SunState {
+    let today = now.naive_local().date();
+
+    // Polar day/night: the sun never crosses the sunrise/sunset threshold at
+    // all today, so there's no boundary to bucket against. Report the grade
+    // directly from the sign of the out-of-range cosine, per the sunrise
+    // equation's edge case.
+    match times_at_depression(lat, lon, today, TWILIGHT_ANGLES[0]) {
+        SolarResult::AlwaysAbove => return SunState::Day,
+        SolarResult::AlwaysBelow => return SunState::Night,
+        SolarResult::Times(_) => {}
+    }
+
+    let mut boundaries = boundaries_on(lat, lon, today.pred_opt().unwrap());
+    boundaries.extend(boundaries_on(lat, lon, today));
+    boundaries.extend(boundaries_on(lat, lon, today.succ_opt().unwrap()));
+    boundaries.sort_by_key(|(t, _)| *t);
+
+    boundaries
+        .into_iter()
+        .rfind(|(t, _)| *t <= now)
+        .map(|(_, grade)| grade)
+        .unwrap_or(SunState::Night)
+}
+
+/// Find the next grade boundary at `(lat, lon)` strictly after `now`,
+/// looking ahead day by day as needed (e.g. near persistent polar day/night).
+pub fn next_transition(lat: f64, lon: f64, now: DateTime<Local>) -> DateTime<Local> {
+    let mut date = now.naive_local().date();
+
+    for _ in 0..366 {
+        if let Some((t, _)) = boundaries_on(lat, lon, date).into_iter().find(|(t, _)| *t > now) {
+            return t;
+        }
+        date = date.succ_opt().unwrap();
+    }
+
+    // Persistent polar day/night: just check again in a day.
+    now + chrono::Duration::days(1)
+}
+
+/// Determine the graded `SunState` right now at `(lat, lon)`, entirely
+/// offline.
+pub fn get_offline_sun_state(lat: f64, lon: f64) -> SunState {
+    grade_at(lat, lon, Local::now())
+}
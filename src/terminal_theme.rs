@@ -0,0 +1,74 @@
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+
+use anyhow::{anyhow, Result};
+use termios::{tcsetattr, Termios, ECHO, ICANON, TCSANOW, VMIN, VTIME};
+
+use crate::SunState;
+
+/// Query the background color over OSC 11 and wait at most this long for the
+/// terminal's reply.
+const REPLY_TIMEOUT_DECISECONDS: u8 = 2;
+
+const QUERY: &[u8] = b"\x1b]11;?\x07";
+
+/// Ask the controlling terminal for its real background color via the OSC 11
+/// escape sequence (`\e]11;?\a`, replied to as `rgb:RRRR/GGGG/BBBB`).
+fn query_background_color() -> Result<(u16, u16, u16)> {
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let fd = tty.as_raw_fd();
+
+    let original = Termios::from_fd(fd)?;
+    let mut raw = original;
+    raw.c_lflag &= !(ICANON | ECHO);
+    raw.c_cc[VMIN] = 0;
+    raw.c_cc[VTIME] = REPLY_TIMEOUT_DECISECONDS;
+    tcsetattr(fd, TCSANOW, &raw)?;
+
+    let outcome = (|| -> Result<(u16, u16, u16)> {
+        (&tty).write_all(QUERY)?;
+
+        let mut buf = [0u8; 64];
+        let n = (&tty).read(&mut buf)?;
+        parse_osc_11_reply(&buf[..n])
+    })();
+
+    tcsetattr(fd, TCSANOW, &original)?;
+
+    outcome
+}
+
+fn parse_osc_11_reply(reply: &[u8]) -> Result<(u16, u16, u16)> {
+    let text = String::from_utf8_lossy(reply);
+    let payload = text
+        .split("rgb:")
+        .nth(1)
+        .ok_or_else(|| anyhow!("terminal reply had no rgb: payload: {:?}", text))?;
+
+    let channels: Vec<&str> = payload
+        .trim_end_matches(['\u{7}', '\u{1b}', '\\'])
+        .split('/')
+        .collect();
+
+    if channels.len() != 3 {
+        return Err(anyhow!("malformed rgb payload: {}", payload));
+    }
+
+    let channel = |s: &str| -> Result<u16> { Ok(u16::from_str_radix(s, 16)?) };
+    Ok((channel(channels[0])?, channel(channels[1])?, channel(channels[2])?))
+}
+
+/// Classify the terminal's current background as `Day` (light) or `Night`
+/// (dark) by perceived luminance, so `auto_daytime` can follow the
+/// terminal's own appearance (e.g. OS-driven) instead of only the sun.
+pub fn get_terminal_sun_state() -> Result<SunState> {
+    let (r, g, b) = query_background_color()?;
+
+    let r = r as f64 / u16::MAX as f64;
+    let g = g as f64 / u16::MAX as f64;
+    let b = b as f64 / u16::MAX as f64;
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+
+    Ok(if luminance > 0.5 { SunState::Day } else { SunState::Night })
+}